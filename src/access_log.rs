@@ -0,0 +1,60 @@
+// Structured access logging middleware.
+//
+// Previously each handler logged a single hand-written `info!("Invoking …")`
+// line with nothing about outcome, status, or latency. This middleware logs
+// one structured record per request on completion instead — method, path,
+// response status, and wall-clock duration — with verbosity controlled by
+// `settings.request_log` (`off`/`basic`/`verbose`). In `verbose` mode it
+// additionally logs the request body of `/generate` and `/recentre`, i.e.
+// the fractal parameters as the raw JSON text the client sent; the body
+// is not parsed here. That is the detail that actually matters when
+// diagnosing which parameter sets cause slow renders.
+
+use actix_web::body::MessageBody;
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_web::{web, Error, HttpMessage};
+use log::info;
+use std::time::Instant;
+
+use crate::settings::RequestLog;
+use crate::SETTINGS;
+
+// Passed to `actix_web::middleware::from_fn`. Buffers and re-injects the
+// request body only when verbose logging is on and the route is one we
+// care about, so the common case pays no extra cost.
+pub async fn log_request(mut req: ServiceRequest, next: Next<impl MessageBody + 'static>) -> Result<ServiceResponse<impl MessageBody>, Error> {
+    let level = SETTINGS.lock().unwrap().request_log.clone();
+    let method = req.method().clone();
+    let path = req.path().to_string();
+    let start = Instant::now();
+
+    let verbose_route = matches!(level, RequestLog::Verbose) && (path == "/generate" || path == "/recentre");
+    let body_snippet = if verbose_route {
+        let bytes = req.extract::<web::Bytes>().await.unwrap_or_default();
+        // Re-inject the buffered body so the downstream handler can still
+        // deserialize it as normal.
+        req.set_payload(Payload::from(bytes.clone()));
+        Some(String::from_utf8_lossy(&bytes).into_owned())
+    } else {
+        None
+    };
+
+    let res = next.call(req).await?;
+    let duration = start.elapsed();
+
+    match level {
+        RequestLog::Off => {}
+        RequestLog::Basic => {
+            info!("{} {} -> {} ({:?})", method, path, res.status(), duration);
+        }
+        RequestLog::Verbose => {
+            match &body_snippet {
+                Some(body) => info!("{} {} -> {} ({:?}) params={}", method, path, res.status(), duration, body),
+                None => info!("{} {} -> {} ({:?})", method, path, res.status(), duration),
+            }
+        }
+    }
+
+    Ok(res)
+}
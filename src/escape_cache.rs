@@ -0,0 +1,382 @@
+// Cache escape-time divergence data to a compact binary file so palettes
+// and colouring modes can be re-applied without recomputing a (possibly
+// very expensive) deep zoom.
+//
+// Neighbouring pixels usually share, or nearly share, an escape count, so
+// each row is delta-coded against its previous cell and run-length
+// encoded, then the resulting byte stream is range-coded against a
+// static order-0 byte frequency model. `save_escape_data` writes all of
+// that out alongside `rows`/`cols`/`mid_pt`/`pt_div`/`max_its`/
+// `FractalKind`; `load_escape_data` reverses it to rebuild `escape_its`
+// so a caller can jump straight to `render_image`/`divergence_histogram`.
+
+use crate::settings::FractalKind;
+use num_complex::Complex;
+use std::fs::File;
+use std::io::{self, Read, Write};
+
+const MAGIC: &[u8; 4] = b"FRC1";
+const TOTAL_FREQ: u32 = 1 << 16;
+
+// Everything needed to reconstruct a `Fractal`'s divergence state,
+// independent of the `Fractal` struct itself so this module doesn't need
+// to know about any of its other fields.
+pub struct EscapeData {
+    pub rows: u32,
+    pub cols: u32,
+    pub mid_pt: Complex<f64>,
+    pub pt_div: f64,
+    pub max_its: u32,
+    pub fractal_kind: FractalKind,
+    pub julia_c: Complex<f64>,
+    pub multibrot_power: u32,
+    pub escape_its: Vec<Vec<u32>>,
+}
+
+fn fractal_kind_to_byte(kind: FractalKind) -> u8 {
+    match kind {
+        FractalKind::Mandelbrot => 0,
+        FractalKind::Julia => 1,
+        FractalKind::BurningShip => 2,
+        FractalKind::Tricorn => 3,
+        FractalKind::Multibrot => 4,
+    }
+}
+
+fn byte_to_fractal_kind(b: u8) -> io::Result<FractalKind> {
+    match b {
+        0 => Ok(FractalKind::Mandelbrot),
+        1 => Ok(FractalKind::Julia),
+        2 => Ok(FractalKind::BurningShip),
+        3 => Ok(FractalKind::Tricorn),
+        4 => Ok(FractalKind::Multibrot),
+        _ => Err(io::Error::new(io::ErrorKind::InvalidData, "Unknown fractal kind byte in escape cache file")),
+    }
+}
+
+fn zigzag_encode(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+
+fn zigzag_decode(v: u64) -> i64 {
+    ((v >> 1) as i64) ^ -((v & 1) as i64)
+}
+
+fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    loop {
+        let byte = (v & 0x7f) as u8;
+        v >>= 7;
+        if v == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn read_varint(data: &[u8], pos: &mut usize) -> u64 {
+    let mut result = 0u64;
+    let mut shift = 0;
+    loop {
+        let byte = data[*pos];
+        *pos += 1;
+        result |= ((byte & 0x7f) as u64) << shift;
+        if byte & 0x80 == 0 {
+            break;
+        }
+        shift += 7;
+    }
+    result
+}
+
+// Delta + run-length encodes one row of escape counts into a varint byte
+// stream of (zigzag delta, run length) pairs.
+fn rle_encode_row(row: &[u32]) -> Vec<u8> {
+    let mut out = Vec::new();
+    let mut prev = 0i64;
+    let mut i = 0;
+    while i < row.len() {
+        let delta = row[i] as i64 - prev;
+        let mut run = 1usize;
+        let mut running = row[i] as i64;
+        while i + run < row.len() && row[i + run] as i64 - running == delta {
+            running = row[i + run] as i64;
+            run += 1;
+        }
+        prev = running;
+        write_varint(&mut out, zigzag_encode(delta));
+        write_varint(&mut out, run as u64);
+        i += run;
+    }
+    out
+}
+
+fn rle_decode_row(data: &[u8], cols: usize) -> Vec<u32> {
+    let mut row = Vec::with_capacity(cols);
+    let mut pos = 0;
+    let mut prev = 0i64;
+    while row.len() < cols {
+        let delta = zigzag_decode(read_varint(data, &mut pos));
+        let run = read_varint(data, &mut pos);
+        for _ in 0..run {
+            prev += delta;
+            row.push(prev as u32);
+        }
+    }
+    row
+}
+
+// Builds a static order-0 byte frequency model, scaled so every count
+// sums to exactly `TOTAL_FREQ` (required by the range coder below), with
+// every observed symbol kept at a frequency of at least 1.
+fn build_model(data: &[u8]) -> ([u32; 256], [u32; 257]) {
+    let mut counts = [0u32; 256];
+    for &b in data {
+        counts[b as usize] += 1;
+    }
+    if data.is_empty() {
+        counts[0] = 1;
+    }
+    let total: u64 = counts.iter().map(|&c| c as u64).sum();
+
+    let mut freqs = [0u32; 256];
+    let mut allocated = 0u32;
+    for i in 0..256 {
+        if counts[i] > 0 {
+            let f = ((counts[i] as u64 * TOTAL_FREQ as u64) / total).max(1) as u32;
+            freqs[i] = f;
+            allocated += f;
+        }
+    }
+    let biggest = (0..256).max_by_key(|&i| counts[i]).unwrap();
+    if allocated <= TOTAL_FREQ {
+        freqs[biggest] += TOTAL_FREQ - allocated;
+    } else {
+        freqs[biggest] = freqs[biggest].saturating_sub(allocated - TOTAL_FREQ).max(1);
+    }
+
+    let mut cum = [0u32; 257];
+    for i in 0..256 {
+        cum[i + 1] = cum[i] + freqs[i];
+    }
+    (freqs, cum)
+}
+
+// Subbotin-style carryless byte range coder.
+struct RangeEncoder {
+    low: u32,
+    range: u32,
+    out: Vec<u8>,
+}
+
+impl RangeEncoder {
+    fn new() -> Self {
+        RangeEncoder { low: 0, range: 0xFFFF_FFFF, out: Vec::new() }
+    }
+
+    fn encode(&mut self, cum_freq: u32, freq: u32) {
+        self.range /= TOTAL_FREQ;
+        self.low = self.low.wrapping_add(cum_freq.wrapping_mul(self.range));
+        self.range = self.range.wrapping_mul(freq);
+        while (self.low ^ self.low.wrapping_add(self.range)) < (1 << 24)
+            || (self.range < (1 << 16) && {
+                self.range = (!self.low).wrapping_add(1) & ((1 << 16) - 1);
+                true
+            })
+        {
+            self.out.push((self.low >> 24) as u8);
+            self.low <<= 8;
+            self.range <<= 8;
+        }
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        for _ in 0..4 {
+            self.out.push((self.low >> 24) as u8);
+            self.low <<= 8;
+        }
+        self.out
+    }
+}
+
+struct RangeDecoder<'a> {
+    low: u32,
+    range: u32,
+    code: u32,
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> RangeDecoder<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        let mut code = 0u32;
+        let mut pos = 0;
+        for _ in 0..4 {
+            code = (code << 8) | *data.get(pos).unwrap_or(&0) as u32;
+            pos += 1;
+        }
+        RangeDecoder { low: 0, range: 0xFFFF_FFFF, code, data, pos }
+    }
+
+    fn get_freq(&mut self) -> u32 {
+        self.range /= TOTAL_FREQ;
+        self.code.wrapping_sub(self.low) / self.range
+    }
+
+    fn decode(&mut self, cum_freq: u32, freq: u32) {
+        self.low = self.low.wrapping_add(cum_freq.wrapping_mul(self.range));
+        self.range = self.range.wrapping_mul(freq);
+        while (self.low ^ self.low.wrapping_add(self.range)) < (1 << 24)
+            || (self.range < (1 << 16) && {
+                self.range = (!self.low).wrapping_add(1) & ((1 << 16) - 1);
+                true
+            })
+        {
+            self.code = (self.code << 8) | *self.data.get(self.pos).unwrap_or(&0) as u32;
+            self.pos += 1;
+            self.low <<= 8;
+            self.range <<= 8;
+        }
+    }
+}
+
+fn range_encode(data: &[u8]) -> ([u32; 256], Vec<u8>) {
+    let (freqs, cum) = build_model(data);
+    let mut encoder = RangeEncoder::new();
+    for &b in data {
+        encoder.encode(cum[b as usize], freqs[b as usize]);
+    }
+    (freqs, encoder.finish())
+}
+
+fn range_decode(freqs: &[u32; 256], encoded: &[u8], out_len: usize) -> Vec<u8> {
+    let mut cum = [0u32; 257];
+    for i in 0..256 {
+        cum[i + 1] = cum[i] + freqs[i];
+    }
+
+    let mut decoder = RangeDecoder::new(encoded);
+    let mut out = Vec::with_capacity(out_len);
+    for _ in 0..out_len {
+        let target = decoder.get_freq();
+        let symbol = (0..256).find(|&i| cum[i] <= target && target < cum[i + 1]).unwrap_or(255);
+        decoder.decode(cum[symbol], freqs[symbol]);
+        out.push(symbol as u8);
+    }
+    out
+}
+
+fn write_u32(out: &mut Vec<u8>, v: u32) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+fn write_f64(out: &mut Vec<u8>, v: f64) {
+    out.extend_from_slice(&v.to_le_bytes());
+}
+
+// Writes `data` to `path` in the format described at the top of this file.
+pub fn save_escape_data(path: &str, data: &EscapeData) -> io::Result<()> {
+    let mut rle_rows = Vec::with_capacity(data.rows as usize);
+    for row in &data.escape_its {
+        rle_rows.push(rle_encode_row(row));
+    }
+    let row_lengths: Vec<u32> = rle_rows.iter().map(|r| r.len() as u32).collect();
+    let concatenated: Vec<u8> = rle_rows.into_iter().flatten().collect();
+    let (freqs, encoded) = range_encode(&concatenated);
+
+    let mut out = Vec::new();
+    out.extend_from_slice(MAGIC);
+    write_u32(&mut out, data.rows);
+    write_u32(&mut out, data.cols);
+    write_f64(&mut out, data.mid_pt.re);
+    write_f64(&mut out, data.mid_pt.im);
+    write_f64(&mut out, data.pt_div);
+    write_u32(&mut out, data.max_its);
+    out.push(fractal_kind_to_byte(data.fractal_kind));
+    write_f64(&mut out, data.julia_c.re);
+    write_f64(&mut out, data.julia_c.im);
+    write_u32(&mut out, data.multibrot_power);
+
+    for &len in &row_lengths {
+        write_u32(&mut out, len);
+    }
+    for &f in &freqs {
+        write_u32(&mut out, f);
+    }
+    write_u32(&mut out, encoded.len() as u32);
+    out.extend_from_slice(&encoded);
+
+    let mut file = File::create(path)?;
+    file.write_all(&out)
+}
+
+// Slices `n` bytes out of `buf` starting at `*pos`, advancing `*pos` past
+// them. A closure can't express this signature: the returned slice's
+// lifetime is tied to `buf`, not to the shorter-lived `&mut usize`, and
+// elision can't pick that out among several differing input lifetimes the
+// way a `fn`'s explicit `'a` can.
+fn read_bytes<'a>(buf: &'a [u8], pos: &mut usize, n: usize) -> &'a [u8] {
+    let slice = &buf[*pos..*pos + n];
+    *pos += n;
+    slice
+}
+
+// Reads a file written by `save_escape_data` and reconstructs its
+// `EscapeData`, ready to drop straight into a `Fractal`.
+pub fn load_escape_data(path: &str) -> io::Result<EscapeData> {
+    let mut buf = Vec::new();
+    File::open(path)?.read_to_end(&mut buf)?;
+
+    let mut pos = 0usize;
+
+    if read_bytes(&buf, &mut pos, 4) != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Not an escape-cache file"));
+    }
+
+    let rows = u32::from_le_bytes(read_bytes(&buf, &mut pos, 4).try_into().unwrap());
+    let cols = u32::from_le_bytes(read_bytes(&buf, &mut pos, 4).try_into().unwrap());
+    let mid_re = f64::from_le_bytes(read_bytes(&buf, &mut pos, 8).try_into().unwrap());
+    let mid_im = f64::from_le_bytes(read_bytes(&buf, &mut pos, 8).try_into().unwrap());
+    let pt_div = f64::from_le_bytes(read_bytes(&buf, &mut pos, 8).try_into().unwrap());
+    let max_its = u32::from_le_bytes(read_bytes(&buf, &mut pos, 4).try_into().unwrap());
+    let fractal_kind = byte_to_fractal_kind(read_bytes(&buf, &mut pos, 1)[0])?;
+    let julia_c_re = f64::from_le_bytes(read_bytes(&buf, &mut pos, 8).try_into().unwrap());
+    let julia_c_im = f64::from_le_bytes(read_bytes(&buf, &mut pos, 8).try_into().unwrap());
+    let multibrot_power = u32::from_le_bytes(read_bytes(&buf, &mut pos, 4).try_into().unwrap());
+
+    let mut row_lengths = Vec::with_capacity(rows as usize);
+    for _ in 0..rows {
+        row_lengths.push(u32::from_le_bytes(read_bytes(&buf, &mut pos, 4).try_into().unwrap()));
+    }
+
+    let mut freqs = [0u32; 256];
+    for f in freqs.iter_mut() {
+        *f = u32::from_le_bytes(read_bytes(&buf, &mut pos, 4).try_into().unwrap());
+    }
+
+    let encoded_len = u32::from_le_bytes(read_bytes(&buf, &mut pos, 4).try_into().unwrap()) as usize;
+    let encoded = read_bytes(&buf, &mut pos, encoded_len);
+
+    let total_rle_bytes: usize = row_lengths.iter().map(|&l| l as usize).sum();
+    let decoded = range_decode(&freqs, encoded, total_rle_bytes);
+
+    let mut escape_its = Vec::with_capacity(rows as usize);
+    let mut offset = 0usize;
+    for &len in &row_lengths {
+        let row_bytes = &decoded[offset..offset + len as usize];
+        escape_its.push(rle_decode_row(row_bytes, cols as usize));
+        offset += len as usize;
+    }
+
+    Ok(EscapeData {
+        rows,
+        cols,
+        mid_pt: Complex::new(mid_re, mid_im),
+        pt_div,
+        max_its,
+        fractal_kind,
+        julia_c: Complex::new(julia_c_re, julia_c_im),
+        multibrot_power,
+        escape_its,
+    })
+}
@@ -16,7 +16,12 @@ use std::io::{self};
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
-use crate::settings::Settings;
+use crate::escape_cache::{self, EscapeData};
+use crate::gpu;
+use crate::jobs::RowProgress;
+use crate::perturbation;
+use crate::quantize;
+use crate::settings::{ColorMode, ComputeBackend, FractalKind, Settings};
 use crate::SETTINGS;
 
 // Error result enum.
@@ -67,6 +72,10 @@ pub struct Fractal {
     pub histogram_duration: Duration,
     pub image_filename: String,
     pub histogram_data_json: String,
+    pub row_progress: Arc<RowProgress>,
+    pub fractal_kind: FractalKind,
+    pub julia_c: Complex<f64>,
+    pub multibrot_power: u32,
 }
 
 // Initialise all struct variables.
@@ -77,6 +86,9 @@ impl Fractal {
 
         // Lock the global SETTINGS to obtain access to the Settings object.
         let settings = SETTINGS.lock().unwrap().clone();
+        let fractal_kind = settings.fractal_kind;
+        let julia_c = Complex::new(settings.julia_c_re, settings.julia_c_im);
+        let multibrot_power = settings.multibrot_power;
 
         Fractal {
             settings: settings,
@@ -96,6 +108,10 @@ impl Fractal {
             histogram_duration: Duration::new(0, 0),
             image_filename: String::from(""),
             histogram_data_json: String::from(""),
+            row_progress: Arc::new(RowProgress::new()),
+            fractal_kind,
+            julia_c,
+            multibrot_power,
         }
     }
 
@@ -162,27 +178,71 @@ impl Fractal {
 
         // Initialise timer for function.
         let generate_start = Instant::now();
-    
-        // Wrap escape_its in an Arc<Mutex<_>> for thread-safe mutable access.
-        let escape_its = Arc::new(Mutex::new(vec![vec![0; self.cols as usize]; self.rows as usize]));
-    
-        // Use parallel iteration over rows.
-        (0..self.rows).into_par_iter().for_each(|row| {
-            let mut st_c = self.pt_lt;
-            st_c.im -= self.pt_div * row as f64;
-
-            // Calculate divergence for row.
-            let mut row_data = vec![0; self.cols as usize];
-            self.cal_row_divergence(row as usize, st_c, &mut row_data);
-    
-            // Lock the Mutex to safely access and modify escape_its.
-            let mut escape_its_locked = escape_its.lock().unwrap();
-            escape_its_locked[row as usize] = row_data;
-        });
-    
-        // After the parallel processing, escape_its can now be safely updated.   
-        // Reassign the computed escape_its back to self.
-        self.escape_its = Arc::try_unwrap(escape_its).unwrap().into_inner().unwrap();
+
+        // Reset the shared row-completion counter so a polling `GET /jobs/{id}`
+        // sees progress climb from 0 for this render.
+        self.row_progress.reset(self.rows);
+
+        // The GPU and perturbation backends only implement the plain
+        // Mandelbrot recurrence (`divergence.wgsl` and
+        // `perturbation::reference_orbit` both hardcode `z = z*z + c`), so
+        // neither knows about `fractal_kind`/`julia_c`/`multibrot_power`.
+        // Fall back to the CPU path - which does - rather than silently
+        // rendering Mandelbrot under a non-Mandelbrot config.
+        let backend = if self.fractal_kind != FractalKind::Mandelbrot && self.settings.compute_backend != ComputeBackend::Cpu {
+            info!(
+                "compute_backend {:?} doesn't support fractal_kind {:?} yet; falling back to cpu.",
+                self.settings.compute_backend, self.fractal_kind
+            );
+            ComputeBackend::Cpu
+        } else {
+            self.settings.compute_backend.clone()
+        };
+
+        if backend == ComputeBackend::Gpu {
+            // GPU path: the whole grid comes back from one (possibly
+            // tiled) dispatch, so progress jumps straight to done rather
+            // than climbing row by row like the CPU path.
+            self.escape_its = gpu::compute_divergence_gpu(self.rows, self.cols, self.mid_pt, self.pt_div, self.max_its);
+            self.row_progress.reset(self.rows);
+            for _ in 0..self.rows {
+                self.row_progress.row_done();
+            }
+        } else if backend == ComputeBackend::Perturbation {
+            // Perturbation path: one shared reference orbit plus a
+            // parallel per-pixel delta iteration, also reported as done
+            // in one shot rather than row by row.
+            self.escape_its = perturbation::compute_divergence_perturbation(self.rows, self.cols, self.mid_pt, self.pt_div, self.max_its);
+            self.row_progress.reset(self.rows);
+            for _ in 0..self.rows {
+                self.row_progress.row_done();
+            }
+        } else {
+            // Wrap escape_its in an Arc<Mutex<_>> for thread-safe mutable access.
+            let escape_its = Arc::new(Mutex::new(vec![vec![0; self.cols as usize]; self.rows as usize]));
+
+            // Use parallel iteration over rows.
+            (0..self.rows).into_par_iter().for_each(|row| {
+                let mut st_c = self.pt_lt;
+                st_c.im -= self.pt_div * row as f64;
+
+                // Calculate divergence for row.
+                let mut row_data = vec![0; self.cols as usize];
+                self.cal_row_divergence(row as usize, st_c, &mut row_data);
+
+                // Lock the Mutex to safely access and modify escape_its.
+                let mut escape_its_locked = escape_its.lock().unwrap();
+                escape_its_locked[row as usize] = row_data;
+
+                // Report completed-row count so progress can be polled while
+                // the render is still running.
+                self.row_progress.row_done();
+            });
+
+            // After the parallel processing, escape_its can now be safely updated.
+            // Reassign the computed escape_its back to self.
+            self.escape_its = Arc::try_unwrap(escape_its).unwrap().into_inner().unwrap();
+        }
 
         self.generate_duration = generate_start.elapsed();
         info!("Time to perform fractal divergence: {:?}", self.generate_duration);
@@ -209,7 +269,17 @@ impl Fractal {
 
         // Point (col) in row for calculation.
         let mut pt_row = st_c;
-    
+
+        // Power used by this row's recurrence; 2 for every kind except
+        // Multibrot, which iterates z^d + c for a configurable d. The
+        // smooth-iteration normalisation below generalizes to ln(power)
+        // in place of the Mandelbrot-specific LN_2.
+        let power = match self.fractal_kind {
+            FractalKind::Multibrot => self.multibrot_power,
+            _ => 2,
+        };
+        let ln_power = (power as f64).ln();
+
         // Iterante over all the columns in the row.
         for col in 0..self.cols {
             if col > 0 {
@@ -219,15 +289,29 @@ impl Fractal {
             // Define diverges flag and set to false.
             let mut diverges = false;
 
-            // Initialise divergence result to complex 0.
-            let mut px_fn = Complex::new(0.0, 0.0);
+            // Starting value and iteration constant depend on the selected
+            // fractal kind. Julia fixes `c` and starts iterating from the
+            // pixel's own coordinate; every other kind starts from 0 and
+            // uses the pixel's coordinate as `c`.
+            let (mut px_fn, c) = match self.fractal_kind {
+                FractalKind::Julia => (pt_row, self.julia_c),
+                _ => (Complex::new(0.0, 0.0), pt_row),
+            };
 
             // Initialise number of iterations.
             let mut num_its = 1;
 
             // Keep iterating until function diverges.
             while !diverges && (num_its < self.max_its) {
-                px_fn = (px_fn * px_fn) + pt_row;
+                px_fn = match self.fractal_kind {
+                    FractalKind::BurningShip => {
+                        let folded = Complex::new(px_fn.re.abs(), px_fn.im.abs());
+                        (folded * folded) + c
+                    }
+                    FractalKind::Tricorn => (px_fn.conj() * px_fn.conj()) + c,
+                    FractalKind::Multibrot => px_fn.powu(power) + c,
+                    FractalKind::Mandelbrot | FractalKind::Julia => (px_fn * px_fn) + c,
+                };
                 if px_fn.norm() >= 2.0 {
                     diverges = true;
                 } else {
@@ -238,7 +322,7 @@ impl Fractal {
             // Calculate fractional divergence for higher definition.
             let mod_fn = px_fn.norm();
             let mu_log = if mod_fn > consts::E {
-                (mod_fn.ln().ln()) / consts::LN_2
+                (mod_fn.ln().ln()) / ln_power
             } else {
                 0.0
             };
@@ -340,18 +424,45 @@ impl Fractal {
         let cols = self.cols;
         let mut img = RgbImage::new(cols, rows);
 
+        // In equalized mode, look pixels up by their position in the
+        // divergence histogram's cumulative distribution instead of by
+        // raw iteration count, so palette colours are spread evenly by
+        // pixel population rather than bunching up in a narrow band.
+        let cdf = match self.settings.color_mode {
+            ColorMode::Equalized => Some(self.divergence_cdf()),
+            ColorMode::Linear => None,
+        };
+
         // Iterate through rows and columns and
         // set the pixel colour accordingly.
         for y in 0..rows {
             for x in 0..cols{
                 let pt_its: u32 = self.escape_its[y as usize][x as usize];
-                let px_col: Rgb<u8> = det_px_col(pt_its, &self.col_palette);
+                let lookup_its = match &cdf {
+                    Some(cdf) => (cdf[pt_its as usize] * self.max_its as f32) as u32,
+                    None => pt_its,
+                };
+                let px_col: Rgb<u8> = det_px_col(lookup_its, &self.col_palette);
                 img.put_pixel(x, y, px_col);
             }
         }
 
-        // Save the image.
-        let _ = img.save(wrt_path_string.clone());
+        // Save the image, quantizing to an indexed palette first if
+        // configured - much smaller files, at the cost of some banding
+        // that Floyd-Steinberg dithering hides.
+        if self.settings.quantize {
+            // Indexed PNG/GIF palettes only have 256 slots; clamp so an
+            // over-large config value can't overflow the `u8` index and
+            // silently alias distinct palette entries onto each other.
+            let quantize_colors = self.settings.quantize_colors.clamp(1, 256) as usize;
+            let palette = quantize::median_cut_palette(&img, quantize_colors);
+            let indices = quantize::dither_to_indices(&img, &palette);
+            if let Err(e) = quantize::save_indexed_png(&wrt_path_string, cols, rows, &indices, &palette) {
+                info!("Failed to save indexed fractal image: {}", e);
+            }
+        } else {
+            let _ = img.save(wrt_path_string.clone());
+        }
 
         // Save image filename without path for sending to file store.
         self.image_filename = wrt_path_string.clone();
@@ -424,7 +535,111 @@ impl Fractal {
         info!("Time to generate divergence histogram: {:?}", self.histogram_duration);
 
         Ok(())
-    }  
+    }
+
+    // Builds the cumulative distribution of escape counts over all pixels,
+    // `cdf[i] = (sum of counts[j] for j <= i) / total_non_escaped`,
+    // for the histogram-equalized colouring mode. Despite its name,
+    // `total_non_escaped` counts only pixels below `max_its`: pixels that
+    // reached `max_its` without escaping are left out of the total, so a
+    // large interior doesn't squeeze the escaped pixels into a narrow band.
+    // Values are clamped to 1.0.
+    fn divergence_cdf(&self) -> Vec<f32> {
+        let bins = (self.max_its + 1) as usize;
+        let mut counts = vec![0u32; bins];
+        let mut total_non_escaped: u32 = 0;
+
+        for row in &self.escape_its {
+            for &its in row {
+                counts[its as usize] += 1;
+                if its < self.max_its {
+                    total_non_escaped += 1;
+                }
+            }
+        }
+
+        let mut cdf = vec![0.0f32; bins];
+        let mut running: u32 = 0;
+        for (i, count) in counts.into_iter().enumerate() {
+            running += count;
+            cdf[i] = if total_non_escaped > 0 {
+                (running as f32 / total_non_escaped as f32).min(1.0)
+            } else {
+                0.0
+            };
+        }
+
+        cdf
+    }
+
+    // Caches the already-computed `escape_its` (plus enough state to
+    // rebuild it) to a compact binary file under `fractal_folder`, so a
+    // deep zoom's palette or colouring mode can be changed later without
+    // rerunning `generate_fractal`. Returns the path written to, named
+    // `escape-NNN.cache` with the first unused suffix, like `render_image`
+    // does for images (the configured `fractal_filename` is not used).
+    pub fn save_escape_data(&self) -> io::Result<String> {
+        info!("Saving escape-time data to cache file.");
+
+        let wrt_path = PathBuf::from(&self.settings.fractal_folder);
+        std::fs::create_dir_all(&wrt_path).expect("Failed to create fractal folder");
+
+        let mut suffix = 1;
+        let mut wrt_path_string;
+        loop {
+            let filename = format!("escape-{:03}.cache", suffix);
+            let mut full_path = wrt_path.clone();
+            full_path.push(filename);
+            wrt_path_string = full_path.to_string_lossy().into_owned();
+
+            if !Path::new(&wrt_path_string).exists() {
+                break;
+            }
+            suffix += 1;
+        }
+
+        let data = EscapeData {
+            rows: self.rows,
+            cols: self.cols,
+            mid_pt: self.mid_pt,
+            pt_div: self.pt_div,
+            max_its: self.max_its,
+            fractal_kind: self.fractal_kind,
+            julia_c: self.julia_c,
+            multibrot_power: self.multibrot_power,
+            escape_its: self.escape_its.clone(),
+        };
+        escape_cache::save_escape_data(&wrt_path_string, &data)?;
+
+        Ok(wrt_path_string)
+    }
+
+    // Reconstructs a `Fractal` from a file written by `save_escape_data`,
+    // with `escape_its`, `rows`, `cols`, `mid_pt`, `pt_div`, `max_its`,
+    // `fractal_kind`, `julia_c` and `multibrot_power` already populated.
+    // The caller still needs to call `init_col_pallete` and `render_image`
+    // (or `divergence_histogram`) to act on the restored data.
+    pub fn load_escape_data(path: &str) -> io::Result<Self> {
+        info!("Loading escape-time data from cache file.");
+
+        let data = escape_cache::load_escape_data(path)?;
+        let mut fractal = Fractal::init();
+
+        fractal.rows = data.rows;
+        fractal.cols = data.cols;
+        fractal.mid_pt = data.mid_pt;
+        fractal.pt_div = data.pt_div;
+        fractal.max_its = data.max_its;
+        fractal.fractal_kind = data.fractal_kind;
+        fractal.julia_c = data.julia_c;
+        fractal.multibrot_power = data.multibrot_power;
+        // Sets left_lim/top_lim/pt_lt and resizes escape_its to zeros;
+        // the restored counts are assigned straight after.
+        fractal.init_fractal_limits();
+        fractal.escape_its = data.escape_its;
+
+        Ok(fractal)
+    }
 }
 
 // Function to determine the colour of the pixel.
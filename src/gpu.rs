@@ -0,0 +1,172 @@
+// GPU compute backend for escape-time divergence, used when
+// `settings.compute_backend` is `ComputeBackend::Gpu`.
+//
+// Mirrors `Fractal::cal_row_divergence` but runs the iteration on a wgpu
+// compute pipeline instead of per-row rayon threads: each GPU thread
+// computes one pixel's escape count via `z = z*z + c` (see
+// `shaders/divergence.wgsl`), then the grid is copied back in one shot and
+// handed to the existing palette/render stage unchanged.
+//
+// Caveat: the shader's coordinates and iteration are `f32`, so once a zoom is deep enough
+// that neighbouring pixels round to the same `f32` coordinate, the GPU
+// image bands well before the CPU `f64` path would. `compute_backend: cpu`
+// remains the correct choice for deep zooms; GPU is for fast, shallow
+// exploratory renders. Large images are tiled into multiple dispatches so
+// no single storage buffer exceeds `maxStorageBufferBindingSize`.
+
+use bytemuck::{Pod, Zeroable};
+use log::info;
+use num_complex::Complex;
+use wgpu::util::DeviceExt;
+
+// Conservative per-dispatch row count so a tile's output buffer stays well
+// under typical `maxStorageBufferBindingSize` limits even for wide images.
+const MAX_TILE_ROWS: u32 = 2048;
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable)]
+struct Uniforms {
+    rows: u32,
+    cols: u32,
+    row_offset: u32,
+    max_its: u32,
+    mid_re: f32,
+    mid_im: f32,
+    pt_div: f32,
+    full_rows: u32,
+}
+
+// Computes escape-time counts for the whole image on the GPU and returns
+// them in the same `rows x cols` layout as the CPU path's `escape_its`.
+pub fn compute_divergence_gpu(rows: u32, cols: u32, mid_pt: Complex<f64>, pt_div: f64, max_its: u32) -> Vec<Vec<u32>> {
+    futures::executor::block_on(compute_divergence_gpu_async(rows, cols, mid_pt, pt_div, max_its))
+}
+
+async fn compute_divergence_gpu_async(rows: u32, cols: u32, mid_pt: Complex<f64>, pt_div: f64, max_its: u32) -> Vec<Vec<u32>> {
+    let instance = wgpu::Instance::default();
+    let adapter = instance
+        .request_adapter(&wgpu::RequestAdapterOptions::default())
+        .await
+        .expect("No suitable GPU adapter found for compute_backend: gpu");
+    let (device, queue) = adapter
+        .request_device(&wgpu::DeviceDescriptor::default(), None)
+        .await
+        .expect("Failed to create GPU device");
+
+    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some("divergence"),
+        source: wgpu::ShaderSource::Wgsl(include_str!("shaders/divergence.wgsl").into()),
+    });
+
+    let pipeline = device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+        label: Some("divergence_pipeline"),
+        layout: None,
+        module: &shader,
+        entry_point: "main",
+    });
+
+    let mut escape_its = vec![vec![0u32; cols as usize]; rows as usize];
+
+    // Tile large images over multiple dispatches so no single storage
+    // buffer exceeds the device's binding size limit.
+    let mut row_offset = 0;
+    while row_offset < rows {
+        let tile_rows = MAX_TILE_ROWS.min(rows - row_offset);
+        let tile = dispatch_tile(&device, &queue, &pipeline, rows, cols, row_offset, tile_rows, mid_pt, pt_div, max_its).await;
+        for (i, row) in tile.into_iter().enumerate() {
+            escape_its[(row_offset as usize) + i] = row;
+        }
+        row_offset += tile_rows;
+    }
+
+    info!("GPU divergence compute finished for {}x{} image.", rows, cols);
+    escape_its
+}
+
+// Dispatches one tile of `tile_rows` rows starting at `row_offset` and
+// reads the resulting iteration counts back into a `tile_rows x cols` grid.
+#[allow(clippy::too_many_arguments)]
+async fn dispatch_tile(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pipeline: &wgpu::ComputePipeline,
+    full_rows: u32,
+    cols: u32,
+    row_offset: u32,
+    tile_rows: u32,
+    mid_pt: Complex<f64>,
+    pt_div: f64,
+    max_its: u32,
+) -> Vec<Vec<u32>> {
+    let uniforms = Uniforms {
+        rows: tile_rows,
+        cols,
+        row_offset,
+        max_its,
+        mid_re: mid_pt.re as f32,
+        mid_im: mid_pt.im as f32,
+        pt_div: pt_div as f32,
+        full_rows,
+    };
+
+    let uniform_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+        label: Some("divergence_uniforms"),
+        contents: bytemuck::bytes_of(&uniforms),
+        usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+    });
+
+    let buffer_size = (tile_rows as u64) * (cols as u64) * std::mem::size_of::<u32>() as u64;
+    let storage_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("divergence_storage"),
+        size: buffer_size,
+        usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("divergence_readback"),
+        size: buffer_size,
+        usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+        mapped_at_creation: false,
+    });
+
+    let bind_group_layout = pipeline.get_bind_group_layout(0);
+    let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+        label: Some("divergence_bind_group"),
+        layout: &bind_group_layout,
+        entries: &[
+            wgpu::BindGroupEntry { binding: 0, resource: uniform_buffer.as_entire_binding() },
+            wgpu::BindGroupEntry { binding: 1, resource: storage_buffer.as_entire_binding() },
+        ],
+    });
+
+    let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: Some("divergence_encoder") });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: Some("divergence_pass"), timestamp_writes: None });
+        pass.set_pipeline(pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        // 8x8 workgroups tiling the pixel grid.
+        pass.dispatch_workgroups((cols + 7) / 8, (tile_rows + 7) / 8, 1);
+    }
+    encoder.copy_buffer_to_buffer(&storage_buffer, 0, &readback_buffer, 0, buffer_size);
+    queue.submit(Some(encoder.finish()));
+
+    let slice = readback_buffer.slice(..);
+    let (tx, rx) = futures::channel::oneshot::channel();
+    slice.map_async(wgpu::MapMode::Read, move |res| {
+        let _ = tx.send(res);
+    });
+    device.poll(wgpu::Maintain::Wait);
+    rx.await.expect("Readback channel closed").expect("Failed to map GPU readback buffer");
+
+    let data = slice.get_mapped_range();
+    let counts: &[u32] = bytemuck::cast_slice(&data);
+    let mut tile = Vec::with_capacity(tile_rows as usize);
+    for row in 0..tile_rows as usize {
+        let start = row * cols as usize;
+        tile.push(counts[start..start + cols as usize].to_vec());
+    }
+    drop(data);
+    readback_buffer.unmap();
+
+    tile
+}
@@ -0,0 +1,333 @@
+// Background fractal-generation job queue.
+//
+// `generate_fractal()` can take anywhere from milliseconds to minutes
+// depending on `rows`/`cols`/`max_its`, but the `/generate` and `/recentre`
+// endpoints used to run it inline on the actix worker while holding the
+// global `Arc<Mutex<Fractal>>`, so one slow deep-zoom render blocked every
+// other request. Instead, endpoints now enqueue a job here and return a
+// `job_id` straight away; a dedicated worker thread owns the `Fractal` and
+// works through queued jobs one at a time, reporting live progress so the
+// UI can poll `GET /jobs/{id}` and show a progress bar.
+
+use log::info;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::mpsc::{sync_channel, Receiver, SyncSender};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+// Bounds how many finished job records `records` retains. Without this a
+// long-running server leaks one `JobRecord` per `/generate`, `/recentre`
+// or `/animate` call for the life of the process, since nothing ever
+// polls `GET /jobs/{id}` for an old job again.
+const MAX_JOB_RECORDS: usize = 500;
+
+use crate::animation::{self, AnimationPlan};
+use crate::fractals::Fractal;
+use crate::metrics;
+use crate::repo::{NewRender, Repo};
+
+// The three kinds of render work the worker thread can be asked to do.
+// Mirrors the `/generate`, `/recentre` and `/animate` endpoints.
+pub enum JobKind {
+    Generate,
+    Recentre { centre_row: u32, centre_col: u32 },
+    Animate(AnimationPlan),
+}
+
+// A queued unit of work, tagged with the job id it was handed at enqueue time.
+struct JobRequest {
+    id: String,
+    kind: JobKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum JobState {
+    Queued,
+    Running,
+    Done,
+    Error,
+}
+
+// Snapshot of a job's state, returned as-is from `GET /jobs/{id}`.
+#[derive(Debug, Clone, Serialize)]
+pub struct JobRecord {
+    pub status: JobState,
+    pub progress: f32,
+    pub time: String,
+    pub image: String,
+    pub error: String,
+    // Populated for `JobKind::Animate` jobs: one filename per rendered
+    // frame, in frame order. Empty for single-image jobs.
+    pub frames: Vec<String>,
+}
+
+impl JobRecord {
+    fn queued() -> Self {
+        JobRecord {
+            status: JobState::Queued,
+            progress: 0.0,
+            time: String::new(),
+            image: String::new(),
+            error: String::new(),
+            frames: Vec::new(),
+        }
+    }
+}
+
+// Shared row-completion counters that `generate_fractal` updates as it goes,
+// so the currently-running job's progress can be read back without waiting
+// for the whole render to finish.
+pub struct RowProgress {
+    completed_rows: AtomicU32,
+    total_rows: AtomicU32,
+}
+
+impl RowProgress {
+    pub fn new() -> Self {
+        RowProgress {
+            completed_rows: AtomicU32::new(0),
+            total_rows: AtomicU32::new(1),
+        }
+    }
+
+    pub fn reset(&self, total_rows: u32) {
+        self.total_rows.store(total_rows.max(1), Ordering::SeqCst);
+        self.completed_rows.store(0, Ordering::SeqCst);
+    }
+
+    pub fn row_done(&self) {
+        self.completed_rows.fetch_add(1, Ordering::SeqCst);
+    }
+
+    pub fn fraction(&self) -> f32 {
+        let completed = self.completed_rows.load(Ordering::SeqCst) as f32;
+        let total = self.total_rows.load(Ordering::SeqCst) as f32;
+        (completed / total).min(1.0)
+    }
+}
+
+// Owns the job channel and the result table that `GET /jobs/{id}` reads from.
+pub struct JobQueue {
+    sender: SyncSender<JobRequest>,
+    records: Arc<Mutex<HashMap<String, JobRecord>>>,
+    // Job ids in enqueue order, used to evict the oldest finished records
+    // once `records` grows past `MAX_JOB_RECORDS`.
+    order: Mutex<VecDeque<String>>,
+}
+
+impl JobQueue {
+    // Spawns the dedicated worker thread and wires it up to `fractal`.
+    // The worker is the only thing that locks `fractal` for the duration of
+    // a render, so queued jobs serialize on the worker, not on request
+    // latency.
+    pub fn init(fractal: Arc<Mutex<Fractal>>, repo: Arc<Repo>) -> Self {
+        info!("Initialising background fractal job queue.");
+
+        let (sender, receiver): (SyncSender<JobRequest>, Receiver<JobRequest>) = sync_channel(16);
+        let records = Arc::new(Mutex::new(HashMap::new()));
+
+        {
+            let records = records.clone();
+            let repo = repo.clone();
+            std::thread::spawn(move || {
+                for job in receiver {
+                    info!("Job {} starting.", job.id);
+                    records.lock().unwrap().insert(
+                        job.id.clone(),
+                        JobRecord {
+                            status: JobState::Running,
+                            progress: 0.0,
+                            time: String::new(),
+                            image: String::new(),
+                            error: String::new(),
+                            frames: Vec::new(),
+                        },
+                    );
+
+                    let start = Instant::now();
+                    let mut fractal = fractal.lock().unwrap();
+
+                    let record = if let JobKind::Animate(plan) = job.kind {
+                        match animation::render_animation(&mut fractal, &plan) {
+                            Ok(frame_files) => {
+                                let frames: Vec<String> = frame_files
+                                    .iter()
+                                    .map(|path| {
+                                        std::path::Path::new(path)
+                                            .file_name()
+                                            .unwrap_or_default()
+                                            .to_string_lossy()
+                                            .into_owned()
+                                    })
+                                    .collect();
+                                JobRecord {
+                                    status: JobState::Done,
+                                    progress: 1.0,
+                                    time: format!("{:.3} sec", start.elapsed().as_millis() as f64 / 1000.0),
+                                    image: frames.last().cloned().unwrap_or_default(),
+                                    error: String::new(),
+                                    frames,
+                                }
+                            }
+                            Err(e) => {
+                                metrics::GENERATE_FAILURES.inc();
+                                JobRecord {
+                                    status: JobState::Error,
+                                    progress: 1.0,
+                                    time: format!("{:.3} sec", start.elapsed().as_millis() as f64 / 1000.0),
+                                    image: String::new(),
+                                    error: e.to_string(),
+                                    frames: Vec::new(),
+                                }
+                            }
+                        }
+                    } else {
+                        let is_recentre = matches!(job.kind, JobKind::Recentre { .. });
+                        let result = match job.kind {
+                            JobKind::Generate => {
+                                fractal.init_fractal_limits();
+                                fractal.generate_fractal()
+                            }
+                            JobKind::Recentre { centre_row, centre_col } => {
+                                fractal.recentre_fractal(centre_row, centre_col)
+                            }
+                            JobKind::Animate(_) => unreachable!("handled above"),
+                        };
+
+                        metrics::GENERATE_DURATION.observe(fractal.generate_duration.as_secs_f64());
+                        metrics::RENDERING_DURATION.observe(fractal.rendering_duration.as_secs_f64());
+                        if result.is_err() {
+                            if is_recentre {
+                                metrics::RECENTRE_FAILURES.inc();
+                            } else {
+                                metrics::GENERATE_FAILURES.inc();
+                            }
+                        }
+
+                        let duration_str = format!("{:.3} sec", start.elapsed().as_millis() as f64 / 1000.0);
+                        let image_filename = std::path::Path::new(&fractal.image_filename)
+                            .file_name()
+                            .unwrap_or_default()
+                            .to_string_lossy()
+                            .into_owned();
+
+                        match result {
+                            Ok(_) => {
+                                // Persist the completed render to the history
+                                // repository so it can be revisited from the
+                                // gallery later.
+                                let new_render = NewRender {
+                                    rows: fractal.rows,
+                                    cols: fractal.cols,
+                                    centre_re: fractal.mid_pt.re,
+                                    centre_im: fractal.mid_pt.im,
+                                    pt_div: fractal.pt_div,
+                                    max_its: fractal.max_its,
+                                    palette_file: fractal.active_palette_file.clone(),
+                                    image_file: image_filename.clone(),
+                                    duration_secs: fractal.generate_duration.as_secs_f64(),
+                                };
+                                if let Err(e) = futures::executor::block_on(repo.save_render(new_render)) {
+                                    info!("Failed to persist render history: {}", e);
+                                }
+
+                                JobRecord {
+                                    status: JobState::Done,
+                                    progress: 1.0,
+                                    time: duration_str,
+                                    image: image_filename,
+                                    error: String::new(),
+                                    frames: Vec::new(),
+                                }
+                            }
+                            Err(e) => JobRecord {
+                                status: JobState::Error,
+                                progress: 1.0,
+                                time: duration_str,
+                                image: String::new(),
+                                error: e.to_string(),
+                                frames: Vec::new(),
+                            },
+                        }
+                    };
+
+                    info!("Job {} finished with status {:?}.", job.id, record.status);
+                    records.lock().unwrap().insert(job.id.clone(), record);
+                }
+            });
+        }
+
+        JobQueue { sender, records, order: Mutex::new(VecDeque::new()) }
+    }
+
+    // Enqueues a job and returns its id immediately; the caller polls
+    // `status()` for progress and the eventual result.
+    pub fn enqueue(&self, kind: JobKind) -> String {
+        let id = new_job_id();
+        self.records.lock().unwrap().insert(id.clone(), JobRecord::queued());
+        self.order.lock().unwrap().push_back(id.clone());
+        self.evict_old_records();
+        // The channel is bounded so a burst of requests can't grow memory
+        // without limit; a full channel just means the queue is already
+        // backed up, so drop the job and mark its record as an error rather
+        // than block the web worker.
+        if self.sender.try_send(JobRequest { id: id.clone(), kind }).is_err() {
+            let mut record = JobRecord::queued();
+            record.status = JobState::Error;
+            record.error = "Job queue is full, try again shortly.".to_string();
+            self.records.lock().unwrap().insert(id.clone(), record);
+        }
+        id
+    }
+
+    // Evicts the oldest finished (`Done`/`Error`) job records once
+    // `records` grows past `MAX_JOB_RECORDS`, so a long-running server
+    // doesn't accumulate one record per job forever. Stops at the first
+    // still-queued/running entry rather than evicting it out from under
+    // a caller that's polling it.
+    fn evict_old_records(&self) {
+        let mut records = self.records.lock().unwrap();
+        if records.len() <= MAX_JOB_RECORDS {
+            return;
+        }
+        let mut order = self.order.lock().unwrap();
+        while records.len() > MAX_JOB_RECORDS {
+            let Some(oldest) = order.front() else { break };
+            match records.get(oldest).map(|r| r.status) {
+                Some(JobState::Done) | Some(JobState::Error) => {
+                    records.remove(oldest);
+                    order.pop_front();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    // Returns the current state of `id`, merging in live row-progress while
+    // the job is running.
+    pub fn status(&self, id: &str, progress: &RowProgress) -> Option<JobRecord> {
+        let mut record = self.records.lock().unwrap().get(id).cloned()?;
+        if record.status == JobState::Running {
+            record.progress = progress.fraction();
+        }
+        Some(record)
+    }
+}
+
+// Generates a lexicographically-sortable, time-prefixed job id in the
+// spirit of a ULID (millisecond timestamp + random-ish counter), without
+// pulling in a dedicated crate for it.
+fn new_job_id() -> String {
+    use std::sync::atomic::AtomicU64;
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+
+    let millis = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis();
+    let seq = COUNTER.fetch_add(1, Ordering::SeqCst);
+    format!("{:012x}{:08x}", millis, seq)
+}
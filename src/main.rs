@@ -19,11 +19,22 @@ use std::sync::{Arc, Mutex};
 use tokio::fs::File;
 use tokio::io::AsyncReadExt;
 
+use crate::jobs::{JobKind, JobQueue, RowProgress};
+use crate::repo::Repo;
 use crate::settings::Settings;
 use crate::fractals::Fractal;
 
 pub mod settings;
 pub mod fractals;
+pub mod jobs;
+pub mod gpu;
+pub mod metrics;
+pub mod repo;
+pub mod access_log;
+pub mod animation;
+pub mod quantize;
+pub mod perturbation;
+pub mod escape_cache;
 
 // Create a global variable for application settings.
 // This will be available in other files.
@@ -49,7 +60,6 @@ async fn serve_image(_req: HttpRequest, path: web::Path<String>) -> actix_web::R
 // Application start (index) endpoint.
 #[get("/")]
 async fn intro() -> impl Responder {
-    info!("Invoking UI application start endpoint.");
 
     // Get application settings in scope.
     let settings: Settings = SETTINGS.lock().unwrap().clone();
@@ -108,9 +118,12 @@ struct FractalParamsClear {
 }
 
 // Generate fractal image endpoint.
+// Rather than rendering inline, this enqueues a job onto the background
+// job queue and returns its `job_id` straight away; poll `GET /jobs/{id}`
+// for progress and the eventual result.
 #[post("/generate")]
-async fn generate(fractal_params: web::Json<FractalParams>, fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
-    info!("Invoking fractal generation endpoint.");
+async fn generate(fractal_params: web::Json<FractalParams>, fractal: web::Data<Arc<Mutex<Fractal>>>, job_queue: web::Data<Arc<JobQueue>>,) -> impl Responder {
+    metrics::GENERATE_TOTAL.inc();
 
     // Get application settings in scope.
     let settings: Settings = SETTINGS.lock().unwrap().clone();
@@ -166,55 +179,31 @@ async fn generate(fractal_params: web::Json<FractalParams>, fractal: web::Data<A
     // Initialise the colour palette as it may have changed.
     let _ = fractal.init_col_pallete();
 
-    // Generate the fractal image.
-    // Report status and payload to the front end.
-    match fractal.generate_fractal() {
-        Ok(_) => {
-            let gen_time_ms:f64 = fractal.generate_duration.as_millis() as f64 / 1000.0 as f64;
-            let duration_str = format!("{:.3} sec", gen_time_ms);
-
-            // Ensure only the filename (not path) is sent to the frontend.
-            let image_filename = std::path::Path::new(&fractal.image_filename)
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .into_owned();
+    // Reflect the now-committed parameters in the configured-size gauges.
+    metrics::CONFIGURED_ROWS.set(fractal.rows as f64);
+    metrics::CONFIGURED_COLS.set(fractal.cols as f64);
+    metrics::CONFIGURED_MAX_ITS.set(fractal.max_its as f64);
 
-            let response_data = json!({
-                "generation": "True",
-                "time": duration_str,
-                "error": "Success",
-                "params": params,
-                "image": image_filename,
-            });
-
-             // Respond with status to display on UI.
-             HttpResponse::Ok().json(response_data)
-        }
-        Err(e) => {
-            // Fractal generation failed, respond with error.
-            let gen_time_ms:f64 = fractal.generate_duration.as_millis() as f64 / 1000.0 as f64;
-            let duration_str = format!("{:.3} sec", gen_time_ms);
+    // Hand the render off to the background job queue so this request
+    // returns immediately instead of blocking on `generate_fractal()`.
+    let job_id = job_queue.enqueue(JobKind::Generate);
 
-            let response_data = json!({
-                "generation": "False",
-                "time": duration_str,
-                "error": e.to_string(),
-                "params": params,
-            });
+    let response_data = json!({
+        "queued": "True",
+        "job_id": job_id,
+        "params": params,
+    });
 
-             // Respond with status to display on UI.
-             HttpResponse::InternalServerError().json(response_data)
-        }
-    }
+    // Respond with the job id so the UI can poll for progress.
+    HttpResponse::Ok().json(response_data)
 }
 
 // This moves the centre of the fractal and then generates the new fractal image.
 // This could involve (but doesn't) copying parts of the already rendered
 // fractal instead of performing divergence calculations on the whole image.
 #[post("/recentre")]
-async fn recentre(fractal_centre: web::Json<FractalCentre>, fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
-    info!("Invoking fractal recentre endpoint.");
+async fn recentre(fractal_centre: web::Json<FractalCentre>, fractal: web::Data<Arc<Mutex<Fractal>>>, job_queue: web::Data<Arc<JobQueue>>,) -> impl Responder {
+    metrics::RECENTRE_TOTAL.inc();
 
     // Get application settings in scope.
     // Currently not used.
@@ -240,45 +229,17 @@ async fn recentre(fractal_centre: web::Json<FractalCentre>, fractal: web::Data<A
     // Initialise colour palette as it may have changed.
     let _ = fractal.init_col_pallete();
 
-    // Recentre and generate the fractal.
-    // Report status and payload to front end.
-    match fractal.recentre_fractal(centre_row, centre_col) {
-        Ok(_) => {
-            let pan_time_ms:f64 = fractal.generate_duration.as_millis() as f64 / 1000.0 as f64;
-            let duration_str = format!("{:.3} sec", pan_time_ms);
-
-            // Ensure only the filename (not path) is sent to the frontend.
-            let image_filename = std::path::Path::new(&fractal.image_filename)
-                .file_name()
-                .unwrap_or_default()
-                .to_string_lossy()
-                .into_owned();
+    // Hand the recentre/render off to the background job queue so this
+    // request returns immediately instead of blocking on `recentre_fractal()`.
+    let job_id = job_queue.enqueue(JobKind::Recentre { centre_row, centre_col });
 
-            let response_data = json!({
-                "recentred": "True",
-                "time": duration_str,
-                "error": "Success",
-                "image": image_filename,
-            });
-
-             // Respond with status to display on UI.
-             HttpResponse::Ok().json(response_data)
-        }
-        Err(e) => {
-            // Fractal recentre and generation failed, respond with error.
-            let pan_time_ms:f64 = fractal.generate_duration.as_millis() as f64 / 1000.0 as f64;
-            let duration_str = format!("{:.3} sec", pan_time_ms);
-
-            let response_data = json!({
-                "recentred": "False",
-                "time": duration_str,
-                "error": e.to_string(),
-            });
+    let response_data = json!({
+        "queued": "True",
+        "job_id": job_id,
+    });
 
-             // Respond with status to display on UI.
-             HttpResponse::InternalServerError().json(response_data)
-        }
-    }
+    // Respond with the job id so the UI can poll for progress.
+    HttpResponse::Ok().json(response_data)
 }
 
 // Generate a histogram curve plot of iteration divergence count versus
@@ -287,7 +248,7 @@ async fn recentre(fractal_centre: web::Json<FractalCentre>, fractal: web::Data<A
 // render palettes.
 #[get("/histogram")]
 async fn histogram(fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
-    info!("Invoking divergence histogram endpoint.");
+    let _metrics_timer = metrics::HISTOGRAM_DURATION.start_timer();
 
     // Get application settings in scope.
     // Currently not used.
@@ -335,7 +296,7 @@ async fn histogram(fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
 // By default colour palette files are stored in a standard folder.
 #[post("/palette")]
 async fn palette(mut payload: Multipart, fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
-    info!("Invoking active colour palette endpoint.");
+    metrics::PALETTE_TOTAL.inc();
 
     // Get application settings in scope.
     let settings: Settings = SETTINGS.lock().unwrap().clone();
@@ -390,6 +351,7 @@ async fn palette(mut payload: Multipart, fractal: web::Data<Arc<Mutex<Fractal>>>
 
         HttpResponse::Ok().json(response_data)
     } else {
+        metrics::PALETTE_FAILURES.inc();
         HttpResponse::BadRequest().body("No palette file provided")
     }
 }
@@ -402,7 +364,7 @@ async fn palette(mut payload: Multipart, fractal: web::Data<Arc<Mutex<Fractal>>>
 // the default colour palette.
 #[post("/render")]
 async fn render(fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
-    info!("Invoking fractal re-render endpoint.");
+    metrics::RENDER_TOTAL.inc();
 
     // Get application settings in scope.
     // Currently not used.
@@ -436,6 +398,7 @@ async fn render(fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
 
     // Report status and payload to front end.
     let render_time_ms:f64 = fractal.rendering_duration.as_millis() as f64 / 1000.0 as f64;
+    metrics::RENDERING_DURATION.observe(render_time_ms);
     let duration_str = format!("{:.3} sec", render_time_ms);
 
     // Ensure only the filename (not path) is sent to the frontend.
@@ -457,6 +420,107 @@ async fn render(fractal: web::Data<Arc<Mutex<Fractal>>>,) -> impl Responder {
     HttpResponse::Ok().json(response_data)
 }
 
+// Poll the status of a background generate/recentre/animate job.
+// Returns `{status, progress, time, image, error, frames}`; `progress` is
+// only meaningful while `status` is `running`.
+#[get("/jobs/{id}")]
+async fn job_status(path: web::Path<String>, job_queue: web::Data<Arc<JobQueue>>, row_progress: web::Data<Arc<RowProgress>>,) -> impl Responder {
+    let job_id = path.into_inner();
+
+    match job_queue.status(&job_id, &row_progress) {
+        Some(record) => HttpResponse::Ok().json(record),
+        None => HttpResponse::NotFound().json(json!({"error": "Unknown job id"})),
+    }
+}
+
+// Define structure for a keyframe zoom animation request payload.
+#[derive(Deserialize, Serialize, Debug, Clone)]
+struct AnimationParams {
+    mid_pt_start_re: f64,
+    mid_pt_start_im: f64,
+    mid_pt_end_re: f64,
+    mid_pt_end_im: f64,
+    pt_div_start: f64,
+    pt_div_end: f64,
+    frame_count: u32,
+    max_its: u32,
+}
+
+// Enqueues a keyframe zoom animation: a sequence of frames zooming/panning
+// from one keyframe to another, each written through the usual
+// unique-suffix render pipeline.
+#[post("/animate")]
+async fn animate(animation_params: web::Json<AnimationParams>, job_queue: web::Data<Arc<JobQueue>>,) -> impl Responder {
+    let params = animation_params.into_inner();
+
+    let plan = animation::AnimationPlan {
+        mid_pt_start: Complex::new(params.mid_pt_start_re, params.mid_pt_start_im),
+        mid_pt_end: Complex::new(params.mid_pt_end_re, params.mid_pt_end_im),
+        pt_div_start: params.pt_div_start,
+        pt_div_end: params.pt_div_end,
+        frame_count: params.frame_count,
+        base_max_its: params.max_its,
+    };
+
+    let job_id = job_queue.enqueue(JobKind::Animate(plan));
+
+    HttpResponse::Ok().json(json!({ "queued": "True", "job_id": job_id, "params": params }))
+}
+
+// Query parameters accepted by `GET /gallery`.
+#[derive(Deserialize)]
+struct GalleryQuery {
+    page: Option<u32>,
+}
+
+// Lists render history, most recent first, one page at a time.
+// Page size is fixed by `settings.gallery_page_size`.
+#[get("/gallery")]
+async fn gallery(query: web::Query<GalleryQuery>, repo: web::Data<Arc<Repo>>,) -> impl Responder {
+
+    let settings: Settings = SETTINGS.lock().unwrap().clone();
+    let page = query.page.unwrap_or(0);
+
+    match repo.list_renders(page, settings.gallery_page_size).await {
+        Ok(records) => HttpResponse::Ok().json(json!({ "page": page, "renders": records })),
+        Err(e) => HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    }
+}
+
+// Repopulates the `Fractal` parameters from a stored render and re-renders
+// it, so a user can jump straight back to any previous location.
+#[post("/gallery/{id}/load")]
+async fn gallery_load(path: web::Path<i64>, fractal: web::Data<Arc<Mutex<Fractal>>>, job_queue: web::Data<Arc<JobQueue>>, repo: web::Data<Arc<Repo>>,) -> impl Responder {
+    let id = path.into_inner();
+
+    let record = match repo.get_render(id).await {
+        Ok(Some(record)) => record,
+        Ok(None) => return HttpResponse::NotFound().json(json!({ "error": "Unknown render id" })),
+        Err(e) => return HttpResponse::InternalServerError().json(json!({ "error": e.to_string() })),
+    };
+
+    let mut fractal = fractal.lock().unwrap();
+    fractal.rows = record.rows;
+    fractal.cols = record.cols;
+    fractal.mid_pt = Complex::new(record.centre_re, record.centre_im);
+    fractal.pt_div = record.pt_div;
+    fractal.max_its = record.max_its;
+    fractal.init_fractal_limits();
+    let _ = fractal.init_col_pallete();
+
+    let job_id = job_queue.enqueue(JobKind::Generate);
+
+    HttpResponse::Ok().json(json!({ "queued": "True", "job_id": job_id, "params": record }))
+}
+
+// Prometheus scrape endpoint, text-format.
+#[get("/metrics")]
+async fn metrics_endpoint() -> impl Responder {
+    HttpResponse::Ok()
+        .content_type("text/plain; version=0.0.4")
+        .body(metrics::gather_text())
+}
+
 // User help endpoint.
 async fn help(settings: web::Data<Settings>) -> impl Responder {
     // Help endpoint function.
@@ -483,6 +547,9 @@ async fn main() -> std::io::Result<()> {
     // Logging configuration held in log4rs.yml .
     log4rs::init_file("log4rs.yml", Default::default()).unwrap();
 
+    // Register Prometheus metrics before the first request can observe them.
+    metrics::register();
+
     // Get application settings in scope.
     let settings: Settings = SETTINGS.lock().unwrap().clone();
     // Do initial program version logging, mainly as a test.
@@ -492,6 +559,19 @@ async fn main() -> std::io::Result<()> {
     // Call init method to initialise struct.
     let fractal = Arc::new(Mutex::new(Fractal::init()));
 
+    // Hang onto the fractal's row-progress counter so the `/jobs/{id}`
+    // endpoint can read it without locking the fractal itself.
+    let row_progress = fractal.lock().unwrap().row_progress.clone();
+
+    // Render history repository, used to persist completed renders and to
+    // back the `/gallery` endpoints.
+    let repo = Arc::new(Repo::init(&settings.database_url));
+    futures::executor::block_on(repo.migrate()).expect("Failed to migrate render history database");
+
+    // Dedicated worker thread that owns the fractal for the lifetime of a
+    // render, fed by the job queue below.
+    let job_queue = Arc::new(JobQueue::init(fractal.clone(), repo.clone()));
+
     // Check number of threads available for fractal computations.
     info!("Number of threads currently available for fractal processing: {}", rayon::current_num_threads());
 
@@ -500,6 +580,10 @@ async fn main() -> std::io::Result<()> {
         App::new()
             .app_data(web::Data::new(fractal.clone()))
             .app_data(web::Data::new(settings.clone()))
+            .app_data(web::Data::new(job_queue.clone()))
+            .app_data(web::Data::new(row_progress.clone()))
+            .app_data(web::Data::new(repo.clone()))
+            .wrap(actix_web::middleware::from_fn(access_log::log_request))
             .service(fsx::Files::new("/fractals", "./fractals").show_files_listing())
             .service(intro)
             .service(generate)
@@ -507,6 +591,11 @@ async fn main() -> std::io::Result<()> {
             .service(histogram)
             .service(palette)
             .service(render)
+            .service(job_status)
+            .service(animate)
+            .service(gallery)
+            .service(gallery_load)
+            .service(metrics_endpoint)
             .service(actix_files::Files::new("/static", "./static").show_files_listing())
             .route("/help", web::get().to(help))
             .route("/fractals/{filename}", web::get().to(serve_image))
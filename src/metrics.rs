@@ -0,0 +1,71 @@
+// Prometheus metrics for render instrumentation.
+//
+// The `duration_str` values already returned in each endpoint's JSON are
+// only ever seen once per request; this turns the same measurements into
+// proper time-series so a Grafana dashboard can track render latency and
+// failure rate over time, useful for capacity planning around slow
+// deep-zoom renders.
+
+use lazy_static::lazy_static;
+use prometheus::{Encoder, Gauge, Histogram, HistogramOpts, IntCounter, Opts, Registry, TextEncoder};
+
+lazy_static! {
+    pub static ref REGISTRY: Registry = Registry::new();
+
+    pub static ref GENERATE_DURATION: Histogram = Histogram::with_opts(
+        HistogramOpts::new("generate_duration_seconds", "Time to compute a fractal's divergence grid.")
+    ).unwrap();
+    pub static ref RENDERING_DURATION: Histogram = Histogram::with_opts(
+        HistogramOpts::new("rendering_duration_seconds", "Time to render a divergence grid to an image.")
+    ).unwrap();
+    pub static ref HISTOGRAM_DURATION: Histogram = Histogram::with_opts(
+        HistogramOpts::new("histogram_duration_seconds", "Time to build the divergence histogram chart data.")
+    ).unwrap();
+
+    pub static ref GENERATE_TOTAL: IntCounter = IntCounter::new("generate_calls_total", "Total /generate calls.").unwrap();
+    pub static ref GENERATE_FAILURES: IntCounter = IntCounter::new("generate_failures_total", "Total failed generate and animate jobs.").unwrap();
+    pub static ref RECENTRE_TOTAL: IntCounter = IntCounter::new("recentre_calls_total", "Total /recentre calls.").unwrap();
+    pub static ref RECENTRE_FAILURES: IntCounter = IntCounter::new("recentre_failures_total", "Total /recentre failures.").unwrap();
+    pub static ref RENDER_TOTAL: IntCounter = IntCounter::new("render_calls_total", "Total /render calls.").unwrap();
+    pub static ref RENDER_FAILURES: IntCounter = IntCounter::new("render_failures_total", "Total /render failures.").unwrap();
+    pub static ref PALETTE_TOTAL: IntCounter = IntCounter::new("palette_calls_total", "Total /palette calls.").unwrap();
+    pub static ref PALETTE_FAILURES: IntCounter = IntCounter::new("palette_failures_total", "Total /palette failures.").unwrap();
+
+    pub static ref CONFIGURED_MAX_ITS: Gauge = Gauge::with_opts(
+        Opts::new("configured_max_its", "Currently configured maximum iteration count.")
+    ).unwrap();
+    pub static ref CONFIGURED_ROWS: Gauge = Gauge::with_opts(
+        Opts::new("configured_rows", "Currently configured image row count.")
+    ).unwrap();
+    pub static ref CONFIGURED_COLS: Gauge = Gauge::with_opts(
+        Opts::new("configured_cols", "Currently configured image column count.")
+    ).unwrap();
+}
+
+// Registers every metric above with `REGISTRY`. Call once from `main()`
+// before the first request is served.
+pub fn register() {
+    REGISTRY.register(Box::new(GENERATE_DURATION.clone())).unwrap();
+    REGISTRY.register(Box::new(RENDERING_DURATION.clone())).unwrap();
+    REGISTRY.register(Box::new(HISTOGRAM_DURATION.clone())).unwrap();
+    REGISTRY.register(Box::new(GENERATE_TOTAL.clone())).unwrap();
+    REGISTRY.register(Box::new(GENERATE_FAILURES.clone())).unwrap();
+    REGISTRY.register(Box::new(RECENTRE_TOTAL.clone())).unwrap();
+    REGISTRY.register(Box::new(RECENTRE_FAILURES.clone())).unwrap();
+    REGISTRY.register(Box::new(RENDER_TOTAL.clone())).unwrap();
+    REGISTRY.register(Box::new(RENDER_FAILURES.clone())).unwrap();
+    REGISTRY.register(Box::new(PALETTE_TOTAL.clone())).unwrap();
+    REGISTRY.register(Box::new(PALETTE_FAILURES.clone())).unwrap();
+    REGISTRY.register(Box::new(CONFIGURED_MAX_ITS.clone())).unwrap();
+    REGISTRY.register(Box::new(CONFIGURED_ROWS.clone())).unwrap();
+    REGISTRY.register(Box::new(CONFIGURED_COLS.clone())).unwrap();
+}
+
+// Renders all registered metrics in Prometheus text exposition format.
+pub fn gather_text() -> String {
+    let metric_families = REGISTRY.gather();
+    let encoder = TextEncoder::new();
+    let mut buffer = Vec::new();
+    encoder.encode(&metric_families, &mut buffer).unwrap();
+    String::from_utf8(buffer).unwrap_or_default()
+}
@@ -0,0 +1,152 @@
+// Perturbation-theory deep zoom.
+//
+// `Fractal::cal_row_divergence` computes every pixel directly in `f64`,
+// so once `pt_div` shrinks below roughly 1e-15 the image degenerates into
+// blocky noise - the pixel grid is smaller than `f64` can resolve around
+// the centre. This computes one high-precision reference orbit
+// `Z_0 = 0, Z_{n+1} = Z_n^2 + C` at the image centre using an
+// arbitrary-precision complex type, then for each pixel iterates only the
+// small delta from that orbit in `f64`: `delta_{n+1} = 2*Z_n*delta_n +
+// delta_n^2 + delta_c`, with the true orbit `Z_n + delta_n` and escape
+// tested on `|Z_n + delta_n| >= 2`. This keeps per-pixel cost at `f64`
+// while letting zooms go far deeper than direct `f64` iteration can reach.
+//
+// Glitches: if the true orbit `Z_n + delta_n` becomes very small relative
+// to the reference's own `Z_n`, the two have nearly cancelled and the
+// reference no longer describes this pixel's true orbit to useful
+// precision (the classic perturbation "glitch"). When detected, the pixel
+// is rebased: a fresh high-precision reference orbit is computed for this
+// pixel's own (fixed) `C`, continuing from the true orbit value already
+// reached rather than restarting at `Z_0 = 0`, and `delta_c` collapses to
+// zero since the new reference is centred exactly on this pixel.
+
+use num_complex::Complex;
+use rayon::prelude::*;
+use rug::{Complex as RugComplex, Float};
+
+const PRECISION_BITS: u32 = 256;
+// If the true orbit `|Z_n + delta_n|` shrinks to within this factor of
+// `|Z_n|`, the reference and the true orbit have nearly cancelled and
+// perturbation has broken down for this pixel; it must be rebased onto a
+// new reference.
+const GLITCH_RATIO: f64 = 1e-6;
+
+// Computes the orbit `Z_{n+1} = Z_n^2 + c` for up to `max_its` iterations
+// starting from `z0`, at `PRECISION_BITS` of precision, rounding each `Z_n`
+// down to `f64`. Stops early if the orbit itself escapes.
+fn orbit_from(c: &RugComplex, z0: RugComplex, max_its: u32) -> Vec<Complex<f64>> {
+    let mut z = z0;
+    let mut orbit = Vec::with_capacity(max_its as usize + 1);
+    orbit.push(Complex::new(z.real().to_f64(), z.imag().to_f64()));
+
+    for _ in 0..max_its {
+        z = (z.clone() * z.clone()) + c.clone();
+        let re = z.real().to_f64();
+        let im = z.imag().to_f64();
+        orbit.push(Complex::new(re, im));
+        if re * re + im * im >= 4.0 {
+            break;
+        }
+    }
+
+    orbit
+}
+
+// Computes the reference orbit at `(centre_re, centre_im)` to
+// `PRECISION_BITS` of precision, for up to `max_its` iterations, rounding
+// each `Z_n` down to `f64`. Stops early if the orbit itself escapes.
+fn reference_orbit(centre_re: f64, centre_im: f64, max_its: u32) -> Vec<Complex<f64>> {
+    let c = RugComplex::with_val(
+        PRECISION_BITS,
+        (Float::with_val(PRECISION_BITS, centre_re), Float::with_val(PRECISION_BITS, centre_im)),
+    );
+    let z0 = RugComplex::with_val(PRECISION_BITS, (0.0, 0.0));
+    orbit_from(&c, z0, max_its)
+}
+
+// Computes escape-time counts for the whole image using perturbation
+// theory around a single reference orbit at the image centre. Returns
+// the same `rows x cols` layout as the direct CPU path's `escape_its`.
+pub fn compute_divergence_perturbation(rows: u32, cols: u32, mid_pt: Complex<f64>, pt_div: f64, max_its: u32) -> Vec<Vec<u32>> {
+    let reference = reference_orbit(mid_pt.re, mid_pt.im, max_its);
+    let ref_c = RugComplex::with_val(
+        PRECISION_BITS,
+        (Float::with_val(PRECISION_BITS, mid_pt.re), Float::with_val(PRECISION_BITS, mid_pt.im)),
+    );
+
+    (0..rows)
+        .into_par_iter()
+        .map(|row| {
+            let mut row_data = vec![0u32; cols as usize];
+            for col in 0..cols {
+                // The pixel's offset from the image centre, in delta-space.
+                // This must never be folded into an absolute `mid_pt + offset`
+                // sum in plain `f64`: once `pt_div` drops below `mid_pt`'s f64
+                // ULP, that sum rounds away the offset entirely and every
+                // pixel in the row collapses onto the same handful of float
+                // values.
+                let offset = Complex::new(
+                    (col as f64 - cols as f64 / 2.0) * pt_div,
+                    -(row as f64 - rows as f64 / 2.0) * pt_div,
+                );
+                row_data[col as usize] = pixel_divergence(&reference, &ref_c, offset, max_its);
+            }
+            row_data
+        })
+        .collect()
+}
+
+// Iterates one pixel's delta against the shared reference orbit, rebasing
+// onto a fresh local reference whenever the glitch condition (the true
+// orbit nearly cancelling the reference's own `Z_n`) is detected. `offset`
+// is this pixel's position relative to `ref_c`, computed directly from
+// the pixel grid rather than via a lossy absolute subtraction.
+fn pixel_divergence(reference: &[Complex<f64>], ref_c: &RugComplex, offset: Complex<f64>, max_its: u32) -> u32 {
+    // Only allocated once this pixel has actually rebased; until then we
+    // iterate directly against the shared `reference` orbit.
+    let mut local_reference: Option<Vec<Complex<f64>>> = None;
+
+    // `delta_c` is this pixel's offset from the active reference's centre.
+    // While iterating against the shared `reference` that's just `offset`;
+    // a rebase below recomputes the active reference exactly at this
+    // pixel's own (fixed) `C`, so `delta_c` collapses to zero.
+    let mut delta_c = offset;
+    let mut delta = Complex::new(0.0, 0.0);
+    let mut ref_idx = 0usize;
+
+    for num_its in 0..max_its {
+        let active: &[Complex<f64>] = local_reference.as_deref().unwrap_or(reference);
+        let z_n = active.get(ref_idx).copied().unwrap_or_else(|| *active.last().unwrap());
+
+        delta = Complex::new(2.0, 0.0) * z_n * delta + delta * delta + delta_c;
+        // `delta` is now delta_{n+1}, so pair it with Z_{n+1}.
+        let z_next = active.get(ref_idx + 1).copied().unwrap_or_else(|| *active.last().unwrap());
+        let true_z = z_next + delta;
+
+        if true_z.norm() >= 2.0 {
+            return num_its + 1;
+        }
+
+        if true_z.norm() < GLITCH_RATIO * z_next.norm() {
+            // Rebase: the true orbit has nearly cancelled the shared
+            // reference's `Z_n`, so compute a fresh high-precision orbit
+            // for this pixel's own fixed `C = ref_c + offset` (never
+            // formed as a lossy plain-`f64` sum), continuing from the
+            // true orbit value already reached rather than restarting at
+            // `Z_0 = 0`. The new reference is centred exactly on this
+            // pixel, so `delta_c` becomes zero from here on.
+            let pixel_re = ref_c.real().clone() + Float::with_val(PRECISION_BITS, offset.re);
+            let pixel_im = ref_c.imag().clone() + Float::with_val(PRECISION_BITS, offset.im);
+            let pixel_c = RugComplex::with_val(PRECISION_BITS, (pixel_re, pixel_im));
+            let z0 = RugComplex::with_val(PRECISION_BITS, (true_z.re, true_z.im));
+            local_reference = Some(orbit_from(&pixel_c, z0, max_its - num_its));
+            delta_c = Complex::new(0.0, 0.0);
+            delta = Complex::new(0.0, 0.0);
+            ref_idx = 0;
+        } else {
+            ref_idx += 1;
+        }
+    }
+
+    max_its
+}
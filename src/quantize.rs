@@ -0,0 +1,168 @@
+// Adaptive palette quantization (median-cut) with Floyd-Steinberg
+// dithering, for compact indexed PNG output instead of 24-bit RGB. Only
+// indexed PNG is written; this module has no GIF encoder.
+//
+// `render_image` already builds the full-colour buffer; when
+// `settings.quantize` is set this runs a median-cut quantizer over that
+// buffer to build a small palette (`settings.quantize_colors` entries),
+// then maps each pixel to its nearest palette entry, propagating the
+// quantization error to neighbouring pixels via Floyd-Steinberg weights
+// so banding doesn't show. This gives much smaller files than the
+// unconditional RGB PNG.
+
+use image::{Rgb, RgbImage};
+
+// One box of pixels in colour space, as used by the median-cut algorithm.
+struct ColorBox {
+    pixels: Vec<(u8, u8, u8)>,
+}
+
+impl ColorBox {
+    fn range(&self, channel: usize) -> u8 {
+        let (mut lo, mut hi) = (255u8, 0u8);
+        for p in &self.pixels {
+            let v = match channel {
+                0 => p.0,
+                1 => p.1,
+                _ => p.2,
+            };
+            lo = lo.min(v);
+            hi = hi.max(v);
+        }
+        hi - lo
+    }
+
+    fn widest_channel(&self) -> usize {
+        let ranges = [self.range(0), self.range(1), self.range(2)];
+        (0..3).max_by_key(|&c| ranges[c]).unwrap_or(0)
+    }
+
+    fn average(&self) -> (u8, u8, u8) {
+        let n = self.pixels.len().max(1) as u64;
+        let (mut r, mut g, mut b) = (0u64, 0u64, 0u64);
+        for p in &self.pixels {
+            r += p.0 as u64;
+            g += p.1 as u64;
+            b += p.2 as u64;
+        }
+        ((r / n) as u8, (g / n) as u8, (b / n) as u8)
+    }
+
+    // Splits this box in two at the median along its widest axis.
+    fn split(mut self) -> (ColorBox, ColorBox) {
+        let channel = self.widest_channel();
+        self.pixels.sort_by_key(|p| match channel {
+            0 => p.0,
+            1 => p.1,
+            _ => p.2,
+        });
+        let mid = self.pixels.len() / 2;
+        let right = self.pixels.split_off(mid);
+        (ColorBox { pixels: self.pixels }, ColorBox { pixels: right })
+    }
+}
+
+// Builds an (at most) `max_colors`-entry palette: start with every pixel
+// in one box, repeatedly split the box with the largest colour range
+// along its widest RGB axis at the median, until `max_colors` boxes
+// exist, then take each box's average colour as a palette entry.
+pub fn median_cut_palette(img: &RgbImage, max_colors: usize) -> Vec<(u8, u8, u8)> {
+    let pixels: Vec<(u8, u8, u8)> = img.pixels().map(|p| (p[0], p[1], p[2])).collect();
+    let mut boxes = vec![ColorBox { pixels }];
+
+    while boxes.len() < max_colors {
+        let splittable = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, b)| b.pixels.len() > 1)
+            .max_by_key(|(_, b)| b.range(b.widest_channel()))
+            .map(|(i, _)| i);
+
+        let Some(idx) = splittable else { break };
+        let (a, b) = boxes.remove(idx).split();
+        boxes.push(a);
+        boxes.push(b);
+    }
+
+    boxes.iter().map(ColorBox::average).collect()
+}
+
+fn nearest_index(color: (i32, i32, i32), palette: &[(u8, u8, u8)]) -> usize {
+    palette
+        .iter()
+        .enumerate()
+        .min_by_key(|(_, p)| {
+            let dr = color.0 - p.0 as i32;
+            let dg = color.1 - p.1 as i32;
+            let db = color.2 - p.2 as i32;
+            dr * dr + dg * dg + db * db
+        })
+        .map(|(i, _)| i)
+        .unwrap_or(0)
+}
+
+// Maps every pixel in `img` to the nearest entry in `palette`, propagating
+// quantization error to neighbours via Floyd-Steinberg weights (7/16
+// right, 3/16 below-left, 5/16 below, 1/16 below-right). Returns the
+// per-pixel palette indices in row-major order.
+pub fn dither_to_indices(img: &RgbImage, palette: &[(u8, u8, u8)]) -> Vec<u8> {
+    let (width, height) = img.dimensions();
+    let mut error = vec![(0i32, 0i32, 0i32); (width * height) as usize];
+    let mut indices = vec![0u8; (width * height) as usize];
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y * width + x) as usize;
+            let Rgb([r, g, b]) = *img.get_pixel(x, y);
+            let (er, eg, eb) = error[i];
+            let adjusted = (r as i32 + er, g as i32 + eg, b as i32 + eb);
+            let clamped = (adjusted.0.clamp(0, 255), adjusted.1.clamp(0, 255), adjusted.2.clamp(0, 255));
+
+            let idx = nearest_index(clamped, palette);
+            indices[i] = idx as u8;
+            let chosen = palette[idx];
+            let (dr, dg, db) = (
+                adjusted.0 - chosen.0 as i32,
+                adjusted.1 - chosen.1 as i32,
+                adjusted.2 - chosen.2 as i32,
+            );
+
+            let mut spread = |dx: i32, dy: i32, weight: i32| {
+                let (nx, ny) = (x as i32 + dx, y as i32 + dy);
+                if nx >= 0 && nx < width as i32 && ny >= 0 && ny < height as i32 {
+                    let j = (ny as u32 * width + nx as u32) as usize;
+                    error[j].0 += dr * weight / 16;
+                    error[j].1 += dg * weight / 16;
+                    error[j].2 += db * weight / 16;
+                }
+            };
+
+            spread(1, 0, 7);
+            spread(-1, 1, 3);
+            spread(0, 1, 5);
+            spread(1, 1, 1);
+        }
+    }
+
+    indices
+}
+
+// Writes an indexed PNG (8-bit palette + per-pixel index) to `path`.
+pub fn save_indexed_png(path: &str, width: u32, height: u32, indices: &[u8], palette: &[(u8, u8, u8)]) -> std::io::Result<()> {
+    let file = std::fs::File::create(path)?;
+    let writer = std::io::BufWriter::new(file);
+
+    let mut encoder = png::Encoder::new(writer, width, height);
+    encoder.set_color(png::ColorType::Indexed);
+    encoder.set_depth(png::BitDepth::Eight);
+
+    let mut flat_palette = Vec::with_capacity(palette.len() * 3);
+    for &(r, g, b) in palette {
+        flat_palette.extend_from_slice(&[r, g, b]);
+    }
+    encoder.set_palette(flat_palette);
+
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(indices)?;
+    Ok(())
+}
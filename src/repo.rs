@@ -0,0 +1,177 @@
+// Persistent render history, backed by a deadpool-pooled SQLite connection.
+//
+// The service used to write images into `./fractals` but kept no record of
+// the parameters that produced them, so a past render could never be
+// reopened. This adds a `renders` table capturing each completed render's
+// parameters, palette, image filename and duration, so `GET /gallery` can
+// list history and `POST /gallery/{id}/load` can repopulate a `Fractal`
+// from any previous row and jump straight back to it.
+
+use deadpool_sqlite::{Config, Pool, Runtime};
+use log::info;
+use rusqlite::params;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Debug)]
+pub enum RepoError {
+    Pool(String),
+    Query(String),
+}
+
+impl fmt::Display for RepoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RepoError::Pool(msg) => write!(f, "Render history pool error: {}", msg),
+            RepoError::Query(msg) => write!(f, "Render history query error: {}", msg),
+        }
+    }
+}
+
+// A single completed render, as persisted to and read back from the
+// `renders` table.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RenderRecord {
+    pub id: i64,
+    pub rows: u32,
+    pub cols: u32,
+    pub centre_re: f64,
+    pub centre_im: f64,
+    pub pt_div: f64,
+    pub max_its: u32,
+    pub palette_file: String,
+    pub image_file: String,
+    pub duration_secs: f64,
+}
+
+// The fields of a render that are known before a row id has been assigned.
+pub struct NewRender {
+    pub rows: u32,
+    pub cols: u32,
+    pub centre_re: f64,
+    pub centre_im: f64,
+    pub pt_div: f64,
+    pub max_its: u32,
+    pub palette_file: String,
+    pub image_file: String,
+    pub duration_secs: f64,
+}
+
+// Pluggable render-history repository, currently backed by SQLite via a
+// deadpool connection pool. A Postgres-backed `Repo` could be swapped in
+// behind the same interface without touching callers.
+pub struct Repo {
+    pool: Pool,
+}
+
+impl Repo {
+    pub fn init(database_url: &str) -> Self {
+        info!("Initialising render history database pool at {}.", database_url);
+        let pool = Config::new(database_url)
+            .create_pool(Runtime::Tokio1)
+            .expect("Failed to create render-history connection pool");
+        Repo { pool }
+    }
+
+    // Creates the `renders` table if this is the first run against a fresh
+    // database file.
+    pub async fn migrate(&self) -> Result<(), RepoError> {
+        let conn = self.pool.get().await.map_err(|e| RepoError::Pool(e.to_string()))?;
+        conn.interact(|conn| {
+            conn.execute_batch(
+                "CREATE TABLE IF NOT EXISTS renders (
+                    id INTEGER PRIMARY KEY AUTOINCREMENT,
+                    rows INTEGER NOT NULL,
+                    cols INTEGER NOT NULL,
+                    centre_re REAL NOT NULL,
+                    centre_im REAL NOT NULL,
+                    pt_div REAL NOT NULL,
+                    max_its INTEGER NOT NULL,
+                    palette_file TEXT NOT NULL,
+                    image_file TEXT NOT NULL,
+                    duration_secs REAL NOT NULL
+                )",
+            )
+        })
+        .await
+        .map_err(|e| RepoError::Query(e.to_string()))?
+        .map_err(|e| RepoError::Query(e.to_string()))
+    }
+
+    // Persists a completed render, returning its assigned row id.
+    pub async fn save_render(&self, render: NewRender) -> Result<i64, RepoError> {
+        let conn = self.pool.get().await.map_err(|e| RepoError::Pool(e.to_string()))?;
+        conn.interact(move |conn| -> rusqlite::Result<i64> {
+            conn.execute(
+                "INSERT INTO renders (rows, cols, centre_re, centre_im, pt_div, max_its, palette_file, image_file, duration_secs)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+                params![
+                    render.rows,
+                    render.cols,
+                    render.centre_re,
+                    render.centre_im,
+                    render.pt_div,
+                    render.max_its,
+                    render.palette_file,
+                    render.image_file,
+                    render.duration_secs,
+                ],
+            )?;
+            Ok(conn.last_insert_rowid())
+        })
+        .await
+        .map_err(|e| RepoError::Query(e.to_string()))?
+        .map_err(|e| RepoError::Query(e.to_string()))
+    }
+
+    // Returns one page of render history, most recent first.
+    pub async fn list_renders(&self, page: u32, page_size: u32) -> Result<Vec<RenderRecord>, RepoError> {
+        let conn = self.pool.get().await.map_err(|e| RepoError::Pool(e.to_string()))?;
+        let offset = (page as i64) * (page_size as i64);
+        conn.interact(move |conn| -> rusqlite::Result<Vec<RenderRecord>> {
+            let mut stmt = conn.prepare(
+                "SELECT id, rows, cols, centre_re, centre_im, pt_div, max_its, palette_file, image_file, duration_secs
+                 FROM renders ORDER BY id DESC LIMIT ?1 OFFSET ?2",
+            )?;
+            let rows = stmt.query_map(params![page_size, offset], row_to_record)?;
+            rows.collect()
+        })
+        .await
+        .map_err(|e| RepoError::Query(e.to_string()))?
+        .map_err(|e| RepoError::Query(e.to_string()))
+    }
+
+    // Fetches a single render by id, e.g. to repopulate `Fractal` for a
+    // `POST /gallery/{id}/load`.
+    pub async fn get_render(&self, id: i64) -> Result<Option<RenderRecord>, RepoError> {
+        let conn = self.pool.get().await.map_err(|e| RepoError::Pool(e.to_string()))?;
+        conn.interact(move |conn| -> rusqlite::Result<Option<RenderRecord>> {
+            conn.query_row(
+                "SELECT id, rows, cols, centre_re, centre_im, pt_div, max_its, palette_file, image_file, duration_secs
+                 FROM renders WHERE id = ?1",
+                params![id],
+                row_to_record,
+            )
+            .map(Some)
+            .or_else(|e| if e == rusqlite::Error::QueryReturnedNoRows { Ok(None) } else { Err(e) })
+        })
+        .await
+        .map_err(|e| RepoError::Query(e.to_string()))?
+        .map_err(|e| RepoError::Query(e.to_string()))
+    }
+}
+
+fn row_to_record(row: &rusqlite::Row) -> rusqlite::Result<RenderRecord> {
+    Ok(RenderRecord {
+        id: row.get(0)?,
+        rows: row.get(1)?,
+        cols: row.get(2)?,
+        centre_re: row.get(3)?,
+        centre_im: row.get(4)?,
+        pt_div: row.get(5)?,
+        max_its: row.get(6)?,
+        palette_file: row.get(7)?,
+        image_file: row.get(8)?,
+        duration_secs: row.get(9)?,
+    })
+}
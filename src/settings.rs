@@ -1,5 +1,53 @@
 use serde::{Deserialize};
 
+// Selects the per-pixel iteration used by `Fractal::cal_row_divergence`.
+// All but `Multibrot` iterate with power 2; see `multibrot_power` for the
+// configurable-power case.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FractalKind {
+    Mandelbrot,
+    Julia,
+    BurningShip,
+    Tricorn,
+    Multibrot,
+}
+
+// Selects how `Fractal::render_image` maps an escape count to a palette
+// position. `Linear` is the original `its / max_its` mapping; `Equalized`
+// spreads colours evenly by pixel population via the divergence histogram's
+// cumulative distribution, preserving contrast at any zoom depth.
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ColorMode {
+    Linear,
+    Equalized,
+}
+
+// Controls the verbosity of the per-request access log middleware in
+// `crate::access_log`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum RequestLog {
+    Off,
+    Basic,
+    Verbose,
+}
+
+// Selects the divergence computation path used by `Fractal::generate_fractal`.
+// `Gpu` trades the CPU path's `f64` precision for wgpu-compute speed, so it
+// bands earlier on deep zooms; see `crate::gpu` for the caveat in detail.
+// `Perturbation` trades raw per-pixel precision for a single high-precision
+// reference orbit, letting zooms go far past where direct `f64` iteration
+// degenerates into noise; see `crate::perturbation`.
+#[derive(Debug, Deserialize, Clone, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ComputeBackend {
+    Cpu,
+    Gpu,
+    Perturbation,
+}
+
 #[derive(Debug, Deserialize, Clone)]
 pub struct Settings {
     pub program_name: String,
@@ -15,4 +63,68 @@ pub struct Settings {
     pub init_mid_pt_im: f64,
     pub init_pt_div: f64,
     pub init_max_its: u32,
+
+    // Divergence compute backend, defaults to the CPU/rayon path.
+    #[serde(default = "default_compute_backend")]
+    pub compute_backend: ComputeBackend,
+
+    // Render history SQLite database file path, e.g. "./fractals/history.db".
+    pub database_url: String,
+    #[serde(default = "default_gallery_page_size")]
+    pub gallery_page_size: u32,
+
+    // Per-request access log verbosity, defaults to one line per request.
+    #[serde(default = "default_request_log")]
+    pub request_log: RequestLog,
+
+    // Which fractal formula `Fractal::cal_row_divergence` iterates.
+    #[serde(default = "default_fractal_kind")]
+    pub fractal_kind: FractalKind,
+    // Fixed `c` used by the Julia formula; ignored otherwise.
+    #[serde(default)]
+    pub julia_c_re: f64,
+    #[serde(default)]
+    pub julia_c_im: f64,
+    // Power `d` used by the Multibrot formula (`z^d + c`); ignored otherwise.
+    #[serde(default = "default_multibrot_power")]
+    pub multibrot_power: u32,
+
+    // Palette lookup mode, defaults to the original linear mapping.
+    #[serde(default = "default_color_mode")]
+    pub color_mode: ColorMode,
+
+    // When set, images are quantized to an indexed palette (median-cut +
+    // Floyd-Steinberg dithering) instead of written as 24-bit RGB.
+    #[serde(default)]
+    pub quantize: bool,
+    #[serde(default = "default_quantize_colors")]
+    pub quantize_colors: u32,
+}
+
+fn default_compute_backend() -> ComputeBackend {
+    ComputeBackend::Cpu
+}
+
+fn default_gallery_page_size() -> u32 {
+    20
+}
+
+fn default_request_log() -> RequestLog {
+    RequestLog::Basic
+}
+
+fn default_fractal_kind() -> FractalKind {
+    FractalKind::Mandelbrot
+}
+
+fn default_multibrot_power() -> u32 {
+    2
+}
+
+fn default_color_mode() -> ColorMode {
+    ColorMode::Linear
+}
+
+fn default_quantize_colors() -> u32 {
+    256
 }